@@ -90,6 +90,12 @@ where
             info!("Chain specification: {}", config.chain_spec.name());
             info!("Node name: {}", config.name);
             info!("Roles: {:?}", config.roles);
+            config.custom.vendor_config = custom_args.load_vendor_config()?;
+            config
+                .custom
+                .vendor_config
+                .apply_properties(&config.chain_spec.properties())
+                .map_err(|e| e.to_string())?;
             config.custom.custom_args = custom_args;
             let runtime = RuntimeBuilder::new()
                 .name_prefix("main-tokio-")
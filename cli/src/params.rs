@@ -1,6 +1,7 @@
 use cli::{AugmentClap, GetLogFilter};
+use std::path::PathBuf;
 use structopt::{clap::App, StructOpt};
-use vendor::RunStrategy;
+use vendor::{config::Config as VendorConfig, RunStrategy};
 
 #[derive(Debug, StructOpt, Clone, Default)]
 pub struct VendorCmd {
@@ -10,6 +11,29 @@ pub struct VendorCmd {
     /// Enable sender mode
     #[structopt(long = "sender")]
     pub sender: bool,
+    /// Side chain configuration file (TOML)
+    #[structopt(long = "vendor-config", value_name = "PATH", parse(from_os_str))]
+    pub vendor_config: Option<PathBuf>,
+    /// Reset the checkpoints of a side chain at startup, relaying resumes after BLOCK
+    #[structopt(long = "vendor-reset", value_name = "CHAIN=BLOCK")]
+    pub vendor_reset: Vec<String>,
+}
+
+impl VendorCmd {
+    /// Load and validate the side chain configuration given by `--vendor-config`.
+    pub fn load_vendor_config(&self) -> Result<VendorConfig, String> {
+        let mut config = match self.vendor_config {
+            Some(ref path) => VendorConfig::load(path).map_err(|e| e.to_string())?,
+            None if self.listener || self.sender => {
+                return Err("`--listener` and `--sender` require `--vendor-config <PATH>`".into())
+            }
+            None => VendorConfig::default(),
+        };
+        for reset in &self.vendor_reset {
+            config.reset(reset).map_err(|e| e.to_string())?;
+        }
+        Ok(config)
+    }
 }
 
 impl GetLogFilter for VendorCmd {
@@ -20,7 +44,8 @@ impl GetLogFilter for VendorCmd {
 
 impl AugmentClap for VendorCmd {
     fn augment_clap<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
-        app
+        // the inherent `augment_clap` generated by `StructOpt`, which adds the flags above.
+        VendorCmd::augment_clap(app)
     }
 }
 
@@ -32,3 +57,47 @@ impl Into<RunStrategy> for VendorCmd {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> VendorCmd {
+        let app = <VendorCmd as AugmentClap>::augment_clap(App::new("ladder-node"));
+        let matches = app
+            .get_matches_from_safe(args)
+            .expect("arguments should parse");
+        VendorCmd::from_clap(&matches)
+    }
+
+    #[test]
+    fn should_parse_vendor_flags() {
+        let cmd = parse(&[
+            "ladder-node",
+            "--vendor-config",
+            "vendor.toml",
+            "--listener",
+            "--sender",
+            "--vendor-reset",
+            "kovan=100",
+            "--vendor-reset",
+            "ropsten=200",
+        ]);
+        assert_eq!(cmd.vendor_config, Some(PathBuf::from("vendor.toml")));
+        assert!(cmd.listener);
+        assert!(cmd.sender);
+        assert_eq!(cmd.vendor_reset, vec!["kovan=100", "ropsten=200"]);
+
+        let cmd = parse(&["ladder-node"]);
+        assert_eq!(cmd.vendor_config, None);
+        assert!(!cmd.listener);
+        assert!(!cmd.sender);
+        assert!(cmd.vendor_reset.is_empty());
+    }
+
+    #[test]
+    fn should_require_vendor_config_for_listener() {
+        let cmd = parse(&["ladder-node", "--listener"]);
+        assert!(cmd.load_vendor_config().is_err());
+    }
+}
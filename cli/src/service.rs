@@ -40,7 +40,7 @@ use substrate_service::{
     LightClient, LightComponents, LightExecutor, TaskExecutor,
 };
 use transaction_pool::{self, txpool::Pool as TransactionPool};
-use vendor::{start_vendor, VendorServiceConfig};
+use vendor::{config::Config as VendorConfig, start_vendor, VendorServiceConfig};
 
 construct_simple_protocol! {
     /// Demo protocol attachment for substrate.
@@ -57,6 +57,8 @@ pub struct NodeConfig<F: substrate_service::ServiceFactory> {
     )>,
     inherent_data_providers: InherentDataProviders,
     pub custom_args: VendorCmd,
+    /// side chains loaded from `--vendor-config`
+    pub vendor_config: VendorConfig,
 }
 
 impl<F> Default for NodeConfig<F>
@@ -68,6 +70,7 @@ where
             grandpa_import_setup: None,
             inherent_data_providers: InherentDataProviders::new(),
             custom_args: VendorCmd::default(),
+            vendor_config: VendorConfig::default(),
         }
     }
 }
@@ -89,14 +92,13 @@ construct_service_factory! {
                         let db_path = config.database_path.clone();
                         let keyring = config.keys.first().map_or(Keyring::default(), |key| Keyring::from(key.as_bytes()));
                         let run_args = config.custom.custom_args.clone();
+                        let vendor_config = config.custom.vendor_config.clone();
                         info!("eth signer key: {}", keyring.to_hex());
                         match FullComponents::<Factory>::new(config, executor.clone()) {
                             Ok(service) => {
                                 executor.spawn(start_vendor(
-                                    VendorServiceConfig { kovan_url: "https://kovan.infura.io/v3/5b83a690fa934df09253dd2843983d89".to_string(),
-                                                        ropsten_url: "https://ropsten.infura.io/v3/5b83a690fa934df09253dd2843983d89".to_string(),
-                                                        kovan_address: "690aB411ca08bB0631C49513e10b29691561bB08".to_string(),
-                                                        ropsten_address: "631b6b933Bc56Ebd93e4402aA5583650Fcf74Cc7".to_string(),
+                                    VendorServiceConfig { chains: vendor_config.chains,
+                                                        finality_lag: vendor_config.finality_lag,
                                                         db_path: db_path,
                                                         eth_key: keyring.to_hex(), // sign message
                                                         strategy: run_args.into(),
@@ -21,7 +21,7 @@ pub use std::fmt;
 use parity_codec::{Decode, Encode};
 use rstd::ops::Div;
 
-use support::traits::Currency;
+use support::traits::{Currency, EnsureOrigin};
 
 use signcheck;
 /*
@@ -64,9 +64,11 @@ decl_module! {
 */
             // 解析message --> 以太坊交易的hash tx_hash  abmatrix上的账号who
             //                 该账号的抵押数量amount   整个交易的签名signature_hash
-            let (tx_hash, who, amount, signature_hash,_coin_hash) = Self::split_message(message.clone(),signature);
-            // 整个交易的hash
-            //let message_hash = Decode::decode(&mut &message.encode()[..]).unwrap();
+            Self::verify_relayer(&sender, &message, &signature)?;
+            let (_tx_hash, who, amount, signature_hash,_coin_hash) = Self::split_message(message.clone(),signature);
+            // 整个交易的hash, covers the block and log index of the deposit
+            // so two deposits of one transaction are told apart.
+            let message_hash = T::Hashing::hash_of(&message);
             runtime_io::print("开始判断是否重复抵押");
             // ensure no repeat desposit
             ensure!(Self::despositing_account().iter().find(|&t| t == &who).is_none(), "Cannot deposit if already depositing.");
@@ -75,7 +77,7 @@ decl_module! {
 
             //check the validity and number of signatures
             runtime_io::print("开始检查签名");
-            match  Self::check_signature(sender.clone(), tx_hash, signature_hash, tx_hash){
+            match  Self::check_signature(sender.clone(), message_hash, signature_hash, message_hash){
                 Ok(y) =>  runtime_io::print("ok") ,
                 Err(x) => return Err(x),
             }
@@ -106,6 +108,7 @@ decl_module! {
             // ensure no repeat
             ensure!(Self::intentions_desposit_vec().iter().find(|&t| t == &who).is_none(), "Cannot deposit if already in queue.");
 
+            Self::verify_relayer(&sender, hash.as_ref(), &signature)?;
             //decode the signature
             let signature_hash =  Decode::decode(&mut &signature.encode()[..]).unwrap();
 
@@ -159,8 +162,9 @@ decl_module! {
             let validators = <session::Module<T>>::validators();
             ensure!(validators.contains(&sender),"Not validator");
             // 解析message --> hash  tag  id  amount
+            Self::verify_relayer(&sender, &message, &signature)?;
             let (_tx_hash,who,_amount,signature_hash,_coin_hash) = Self::split_message(message.clone(),signature);
-            let message_hash = Decode::decode(&mut &message.encode()[..]).unwrap();
+            let message_hash = T::Hashing::hash_of(&message);
 
             //check the validity and number of signatures
             runtime_io::print("开始检查签名");
@@ -192,6 +196,7 @@ decl_module! {
             ensure!(!Self::despositing_account().iter().find(|&t| t == &who).is_none(), "Cannot deposit if not depositing.");
             ensure!(Self::intentions_withdraw().iter().find(|&t| t == &who).is_none(), "Cannot withdraw2 if already in withdraw2 queue.");
 
+            Self::verify_relayer(&sender, hash.as_ref(), &signature)?;
             let signature_hash =  Decode::decode(&mut &signature.encode()[..]).unwrap();
             runtime_io::print("开始检查withdraw签名");
             match  Self::check_signature(sender.clone(), hash, signature_hash, hash){
@@ -215,13 +220,16 @@ decl_module! {
         ///   0-5000 5000-50000 50000-500000 500000-->
         ///      x1         x2         x3      x3
         ///     y1      y2         y3          y4
-        fn set_session_reward_factor(_origin,session: Vec<u32>, session_factor: Vec<u8>)-> Result{
+        fn set_session_reward_factor(origin,session: Vec<u32>, session_factor: Vec<u8>)-> Result{
+            T::GovernanceOrigin::ensure_origin(origin)?;
             // session
             ensure!(session.len() >= 3,"not enough session arguments.at least 3");
             ensure!(session_factor.len() >= 4,"not enough session_factor arguments.at least 4");
 
-            <RewardSessionValue<T>>::put(session);
-            <RewardSessionFactor<T>>::put(session_factor);
+            let previous = (Self::reward_session_value(), Self::reward_session_factor());
+            <RewardSessionValue<T>>::put(session.clone());
+            <RewardSessionFactor<T>>::put(session_factor.clone());
+            Self::deposit_event(RawEvent::SessionRewardFactorChanged(previous, (session, session_factor)));
             Ok(())
             // money
         }
@@ -230,30 +238,45 @@ decl_module! {
         ///   0-5000 5000-50000 50000-500000 500000-->
         ///      x1         x2         x3      x3
         ///     y1      y2         y3          y4
-        fn set_balance_reward_factor(_origin, money: Vec<T::Balance>, money_factor: Vec<u8>)-> Result{
+        fn set_balance_reward_factor(origin, money: Vec<T::Balance>, money_factor: Vec<u8>)-> Result{
+            T::GovernanceOrigin::ensure_origin(origin)?;
 
             ensure!(money.len() >= 3,"not enough money arguments.at least 3");
             ensure!(money_factor.len() >= 4,"not enough money_factor arguments.at least 4");
 
-            <RewardBalanceValue<T>>::put(money);
-            <RewardBalanceFactor<T>>::put(money_factor);
+            let previous = (Self::reward_balance_value(), Self::reward_balance_factor());
+            <RewardBalanceValue<T>>::put(money.clone());
+            <RewardBalanceFactor<T>>::put(money_factor.clone());
+            Self::deposit_event(RawEvent::BalanceRewardFactorChanged(previous, (money, money_factor)));
 
             Ok(())
             // money
         }
         /// set session lenth
-        fn set_session_lenth(session_len: u64 ){
+        fn set_session_lenth(origin, session_len: u64) -> Result{
+           T::GovernanceOrigin::ensure_origin(origin)?;
            ensure!(session_len >= 10,"the session lenth must larger than 10");
-           <SessionLength<T>>::put(T::BlockNumber::sa(session_len));
+           let previous = Self::length();
+           let session_len = T::BlockNumber::sa(session_len);
+           <SessionLength<T>>::put(session_len);
+           Self::deposit_event(RawEvent::SessionLengthChanged(previous, session_len));
+           Ok(())
         }
 
+        /// 发放记录的奖励
+        /// Pay the recorded reward of a depositing account.
         pub fn draw_reward(origin,id: T::AccountId) -> Result{
+             T::GovernanceOrigin::ensure_origin(origin)?;
              ensure!(!Self::despositing_account().iter().find(|&t| t == &id).is_none(), "Cannot draw if not depositing.");
 
-             let new_balance = <BalanceOf<T> as As<u64>>::sa(T::Balance::as_(Self::count_draw_reward(id.clone())));
+             let reward = Self::count_draw_reward(id.clone());
+             let new_balance = <BalanceOf<T> as As<u64>>::sa(T::Balance::as_(reward));
              match T::Currency::deposit_into_existing(&id, new_balance){
                  Err(x) => Err(x),
-                 _ => Ok(()),
+                 _ => {
+                     Self::deposit_event(RawEvent::RewardDrawn(id, reward));
+                     Ok(())
+                 }
              }
         }
         /// a new session starts
@@ -336,6 +359,14 @@ decl_event! {
 		AddWithdrawQueue(AccountId),
         /// a new seesion start
         NewRewardSession(BlockNumber),
+        /// The session reward factor changed: the previous and the new values and factors.
+        SessionRewardFactorChanged((Vec<u32>, Vec<u8>), (Vec<u32>, Vec<u8>)),
+        /// The balance reward factor changed: the previous and the new values and factors.
+        BalanceRewardFactorChanged((Vec<Balance>, Vec<u8>), (Vec<Balance>, Vec<u8>)),
+        /// The session length changed: the previous and the new one.
+        SessionLengthChanged(BlockNumber, BlockNumber),
+        /// The recorded reward of an account was paid, its record is now zero.
+        RewardDrawn(AccountId, Balance),
 
     }
 }
@@ -378,15 +409,6 @@ impl<T: Trait> Module<T>
         return (tx_hash,who,amountu64,signature_hash,coint_hash);
     }
 
-    pub fn signature521(signature: Vec<u8>, hash: Vec<u8>){
-        //ensure the signature is valid
-        let mut tx_hash_to_check:[u8;65] = [0;65];
-        tx_hash_to_check.clone_from_slice(&hash);
-        let mut signature_hash_to_check:[u8;32] = [0;32];
-        signature_hash_to_check.clone_from_slice(&signature);
-        Self::check_secp512(&tx_hash_to_check,&signature_hash_to_check).is_ok();
-    }
-
     /// Hook to be called after transaction processing.  间隔一段时间才触发 rotate_session
     pub fn check_rotate_session(block_number: T::BlockNumber) {
         // do this last, after the staking system has had chance to switch out the authorities for the
@@ -573,12 +595,9 @@ impl<T: Trait> Module<T>
         <signcheck::Module<T>>::check_signature(who,tx,signature ,message_hash)
     }
 
-    pub fn check_secp512(signature: &[u8; 65], tx: &[u8; 32]) -> Result {
-        runtime_io::print("asd");
-        //TODO: if runtime_io::secp256k1_ecdsa_recover(signature,tx).is_ok(){ } else {
-        //  return Err(()); }
-        //TODO:
-        Ok(())
+    /// Ensures the message was signed by the Ethereum address registered for the relayer.
+    pub fn verify_relayer(who: &T::AccountId, message: &[u8], signature: &[u8]) -> Result {
+        <signcheck::Module<T>>::verify_relayer(who, message, signature)
     }
 
 
@@ -9,6 +9,7 @@ use support::{
     StorageValue,
 };
 
+use support::traits::EnsureOrigin;
 use system::ensure_signed;
 
 use rstd::marker::PhantomData;
@@ -32,7 +33,8 @@ decl_event!(
         <T as system::Trait>::Hash
     {
         Created(AccountId, Hash),
-        SetMinRequreSignatures(u64),
+        /// The least number of signatures changed: the previous and the new one.
+        SetMinRequreSignatures(u64, u64),
         Txisok(u64),
         // 交易 = vec<id，签名>
         TranscationVerified(u64,Vec<(AccountId,u64)>),
@@ -71,23 +73,26 @@ decl_module! {
         /// 设置最小要求签名数量
         /// Set the minimum required number of signatures
         pub  fn set_min_num(origin,new_num: u64) -> Result{
-            let _sender = ensure_signed(origin)?;
+            T::GovernanceOrigin::ensure_origin(origin)?;
             let newmin = new_num;
-            if newmin < 5 {
-                return Err("too small,should bigger than 5");
+            if newmin < 1 {
+                return Err("too small,should not be 0");
             }
+            let previous = Self::min_signature();
             <MinNumOfSignature<T>>::put(newmin) ;
-            Self::deposit_event(RawEvent::SetMinRequreSignatures(newmin));
+            Self::deposit_event(RawEvent::SetMinRequreSignatures(previous, newmin));
             Ok(())
         }
 
         /// 签名并判断如果当前签名数量足够就发送一个事件
         pub  fn check_exchange(origin, message: Vec<u8>, signature: Vec<u8>) -> Result{
+            let relayer = ensure_signed(origin)?;
+            <bank::Module<T>>::verify_relayer(&relayer, &message, &signature)?;
 
-        let (who , exchangerate  ,time ,extype) = Self::parse_data(message, signature);
-        let sender = who;
-        Self::check_signature(sender,exchangerate,time,extype);
-        Ok(())
+            let (who , exchangerate  ,time ,extype) = Self::parse_data(message, signature);
+            let sender = who;
+            Self::check_signature(sender,exchangerate,time,extype);
+            Ok(())
         }
     }
 }
@@ -197,12 +202,6 @@ impl<T: Trait> Module<T> {
             extype = extype + exp;
             q = q + 1;
         });
-        //ensure the signature is valid
-        let mut tx_hash_to_check: [u8; 65] = [0; 65];
-        tx_hash_to_check.clone_from_slice(&hash);
-        let mut signature_hash_to_check: [u8; 32] = [0; 32];
-        signature_hash_to_check.clone_from_slice(&signature);
-        <bank::Module<T>>::check_secp512(&tx_hash_to_check, &signature_hash_to_check).is_ok();
 
         return (who, rate, time, extype);
     }
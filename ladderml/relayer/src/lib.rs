@@ -0,0 +1,332 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use parity_codec::Encode;
+use primitives::H160;
+use rstd::prelude::*;
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap};
+use system::{ensure_root, ensure_signed};
+
+/// Prefix `signer::Eth::sign_message` signs the messages with, as `eth_sign` does.
+const ETH_MESSAGE_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n";
+
+/// Prefix of the message a validator signs with its Ethereum key to bind it,
+/// followed by the encoded account id.
+pub const BINDING_PREFIX: &[u8] = b"ladder relayer:";
+
+pub trait Trait: session::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        <T as system::Trait>::AccountId
+    {
+        /// An account signs relay messages with this Ethereum address.
+        AddressBound(AccountId, H160),
+        /// An account no longer signs with this Ethereum address.
+        AddressUnbound(AccountId, H160),
+    }
+);
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Relayer {
+        /// 验证者签名用的以太坊地址
+        /// Ethereum address each account signs relay messages with.
+        EthAddressOf get(eth_address_of) : map T::AccountId => Option<H160>;
+
+        /// 以太坊地址属于哪个账号
+        /// Account an Ethereum address is bound to, an address signs for one account only.
+        AccountOf get(account_of) : map H160 => Option<T::AccountId>;
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+
+        fn deposit_event<T>() = default;
+
+        /// 绑定或更换签名用的以太坊地址
+        /// Bind the Ethereum address the sender signs relay messages with, or replace it.
+        /// `proof` is the signature of `BINDING_PREFIX` and the encoded sender by the
+        /// address, which proves the sender holds its key.
+        pub fn bind(origin, address: H160, proof: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            let signer = Self::recover(&Self::eth_message_hash(&Self::binding_message(&sender)), &proof)
+                .ok_or("invalid proof")?;
+            ensure!(signer == address, "proof is not signed by the address");
+            Self::bind_address(sender, address)
+        }
+
+        /// 设置账号签名用的以太坊地址
+        /// Set the Ethereum address of an account, without proof.
+        pub fn set_address(origin, who: T::AccountId, address: H160) -> Result {
+            ensure_root(origin)?;
+            Self::bind_address(who, address)
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    fn bind_address(who: T::AccountId, address: H160) -> Result {
+        if let Some(owner) = Self::account_of(&address) {
+            ensure!(owner == who, "address is bound to another account");
+            return Ok(());
+        }
+        if let Some(previous) = Self::eth_address_of(&who) {
+            <AccountOf<T>>::remove(&previous);
+            Self::deposit_event(RawEvent::AddressUnbound(who.clone(), previous));
+        }
+        <EthAddressOf<T>>::insert(&who, address);
+        <AccountOf<T>>::insert(&address, who.clone());
+        Self::deposit_event(RawEvent::AddressBound(who, address));
+        Ok(())
+    }
+
+    /// The message the Ethereum key of `who` signs to bind it.
+    pub fn binding_message(who: &T::AccountId) -> Vec<u8> {
+        let mut message = BINDING_PREFIX.to_vec();
+        message.extend(who.encode());
+        message
+    }
+
+    /// Ethereum addresses of the validators of the session, in validator order.
+    /// Validators without an address are left out.
+    pub fn validator_addresses() -> Vec<(T::AccountId, H160)> {
+        <session::Module<T>>::validators()
+            .into_iter()
+            .filter_map(|validator| Self::eth_address_of(&validator).map(|address| (validator, address)))
+            .collect()
+    }
+
+    /// The hash `signer::Eth::sign_message` signs: keccak256 of the message
+    /// behind the `eth_sign` prefix and its length in decimal.
+    pub fn eth_message_hash(message: &[u8]) -> [u8; 32] {
+        let mut length = Vec::new();
+        let mut n = message.len();
+        loop {
+            length.insert(0, b'0' + (n % 10) as u8);
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        let mut data = ETH_MESSAGE_PREFIX.to_vec();
+        data.extend_from_slice(&length);
+        data.extend_from_slice(message);
+        runtime_io::keccak_256(&data)
+    }
+
+    /// The Ethereum address that signed `hash`, `None` if the signature is invalid.
+    /// The signature is r, s and v, v being 0 or 1, or 27 or 28 as Ethereum gives it.
+    pub fn recover(hash: &[u8; 32], signature: &[u8]) -> Option<H160> {
+        if signature.len() != 65 {
+            return None;
+        }
+        let mut sig = [0u8; 65];
+        sig.copy_from_slice(signature);
+        if sig[64] >= 27 {
+            sig[64] -= 27;
+        }
+        let public = runtime_io::secp256k1_ecdsa_recover(&sig, hash).ok()?;
+        let public_hash = runtime_io::keccak_256(&public);
+        Some(H160::from_slice(&public_hash[12..]))
+    }
+
+    /// Ensures the message was signed by the Ethereum address bound to `who`.
+    pub fn verify(who: &T::AccountId, message: &[u8], signature: &[u8]) -> Result {
+        let address = Self::eth_address_of(who).ok_or("relayer has no bound address")?;
+        match Self::recover(&Self::eth_message_hash(message), signature) {
+            Some(signer) if signer == address => Ok(()),
+            Some(_) => Err("signature is not from the relayer address"),
+            None => Err("invalid signature"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::{Blake2Hasher, H256};
+    use runtime_io::with_externalities;
+    use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use sr_primitives::{
+        testing::{ConvertUintAuthorityId, Digest, DigestItem, Header, UintAuthorityId},
+        traits::{BlakeTwo256, IdentityLookup},
+        BuildStorage,
+    };
+    use support::{assert_noop, assert_ok, impl_outer_origin};
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<u64>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl consensus::Trait for Test {
+        type Log = DigestItem;
+        type SessionKey = UintAuthorityId;
+        type InherentOfflineReport = ();
+    }
+    impl timestamp::Trait for Test {
+        type Moment = u64;
+        type OnTimestampSet = ();
+    }
+    impl session::Trait for Test {
+        type ConvertAccountIdToSessionKey = ConvertUintAuthorityId;
+        type OnSessionChange = ();
+        type Event = ();
+    }
+    impl Trait for Test {
+        type Event = ();
+    }
+    type Relayer = Module<Test>;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap()
+            .0
+            .into()
+    }
+
+    fn address_of(secret: &[u8; 32]) -> H160 {
+        let secp = Secp256k1::new();
+        let public = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(secret).unwrap());
+        H160::from_slice(&runtime_io::keccak_256(&public.serialize_uncompressed()[1..])[12..])
+    }
+
+    /// Signature of the message as `eth_sign` gives it, v being 27 or 28.
+    fn sign(secret: &[u8; 32], message: &[u8]) -> Vec<u8> {
+        let secp = Secp256k1::new();
+        let hash = Message::from_slice(&Relayer::eth_message_hash(message)).unwrap();
+        let (recovery_id, compact) = secp
+            .sign_recoverable(&hash, &SecretKey::from_slice(secret).unwrap())
+            .serialize_compact();
+        let mut signature = compact.to_vec();
+        signature.push(27 + recovery_id.to_i32() as u8);
+        signature
+    }
+
+    #[test]
+    fn should_bind_with_valid_proof() {
+        with_externalities(&mut new_test_ext(), || {
+            let address = address_of(&[1u8; 32]);
+            let proof = sign(&[1u8; 32], &Relayer::binding_message(&1));
+            assert_ok!(Relayer::bind(Origin::signed(1), address, proof));
+            assert_eq!(Relayer::eth_address_of(1), Some(address));
+            assert_eq!(Relayer::account_of(address), Some(1));
+        });
+    }
+
+    #[test]
+    fn should_reject_invalid_proof() {
+        with_externalities(&mut new_test_ext(), || {
+            let address = address_of(&[1u8; 32]);
+            assert_noop!(
+                Relayer::bind(Origin::signed(1), address, vec![0u8; 64]),
+                "invalid proof"
+            );
+            // signed by another key
+            let proof = sign(&[2u8; 32], &Relayer::binding_message(&1));
+            assert_noop!(
+                Relayer::bind(Origin::signed(1), address, proof),
+                "proof is not signed by the address"
+            );
+            // signed for another account
+            let proof = sign(&[1u8; 32], &Relayer::binding_message(&2));
+            assert_noop!(
+                Relayer::bind(Origin::signed(1), address, proof),
+                "proof is not signed by the address"
+            );
+            assert_eq!(Relayer::eth_address_of(1), None);
+        });
+    }
+
+    #[test]
+    fn should_release_old_address_on_rotation() {
+        with_externalities(&mut new_test_ext(), || {
+            let old = address_of(&[1u8; 32]);
+            let new = address_of(&[2u8; 32]);
+            assert_ok!(Relayer::bind(
+                Origin::signed(1),
+                old,
+                sign(&[1u8; 32], &Relayer::binding_message(&1))
+            ));
+            assert_ok!(Relayer::bind(
+                Origin::signed(1),
+                new,
+                sign(&[2u8; 32], &Relayer::binding_message(&1))
+            ));
+            assert_eq!(Relayer::eth_address_of(1), Some(new));
+            assert_eq!(Relayer::account_of(old), None);
+            assert_eq!(Relayer::account_of(new), Some(1));
+
+            // the released address can be bound again, the taken one can't
+            assert_ok!(Relayer::bind(
+                Origin::signed(2),
+                old,
+                sign(&[1u8; 32], &Relayer::binding_message(&2))
+            ));
+            assert_noop!(
+                Relayer::bind(
+                    Origin::signed(2),
+                    new,
+                    sign(&[2u8; 32], &Relayer::binding_message(&2))
+                ),
+                "address is bound to another account"
+            );
+            assert_eq!(Relayer::account_of(old), Some(2));
+        });
+    }
+
+    #[test]
+    fn should_recover_with_either_recovery_id() {
+        let address = address_of(&[1u8; 32]);
+        let hash = Relayer::eth_message_hash(b"message");
+        let mut signature = sign(&[1u8; 32], b"message");
+        assert!(signature[64] == 27 || signature[64] == 28);
+        assert_eq!(Relayer::recover(&hash, &signature), Some(address));
+        signature[64] -= 27;
+        assert_eq!(Relayer::recover(&hash, &signature), Some(address));
+        assert_eq!(Relayer::recover(&hash, &signature[..64]), None);
+    }
+
+    #[test]
+    fn should_verify_relayer_signature() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Relayer::set_address(
+                system::RawOrigin::Root.into(),
+                1,
+                address_of(&[1u8; 32])
+            ));
+            assert_ok!(Relayer::verify(&1, b"message", &sign(&[1u8; 32], b"message")));
+            assert_eq!(
+                Relayer::verify(&1, b"message", &sign(&[2u8; 32], b"message")),
+                Err("signature is not from the relayer address")
+            );
+            assert_eq!(
+                Relayer::verify(&1, b"message", &[0u8; 65]),
+                Err("invalid signature")
+            );
+            assert_eq!(
+                Relayer::verify(&2, b"message", &sign(&[2u8; 32], b"message")),
+                Err("relayer has no bound address")
+            );
+        });
+    }
+}
@@ -9,7 +9,7 @@ use support::{
     StorageValue,
 };
 
-use system::ensure_signed;
+use support::traits::EnsureOrigin;
 
 use rstd::marker::PhantomData;
 use rstd::prelude::*;
@@ -20,9 +20,14 @@ pub use std::fmt;
 // use Encode, Decode
 use parity_codec::{Decode, Encode};
 use rstd::ops::Div;
+use primitives::H160;
 
-pub trait Trait: system::Trait {
+pub trait Trait: relayer::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+    /// 可以修改参数的来源: sudo 或议会
+    /// Origin that changes the parameters of signcheck, bank and exchange.
+    type GovernanceOrigin: EnsureOrigin<Self::Origin>;
 }
 
 decl_event!(
@@ -32,7 +37,12 @@ decl_event!(
         <T as system::Trait>::Hash
     {
         Created(AccountId, Hash),
-        SetMinRequreSignatures(u64),
+        /// The least number of signatures changed: the previous and the new one.
+        SetMinRequreSignatures(u64, u64),
+        /// The ratio of validators changed: the previous and the new numerator and denominator.
+        ThresholdRatioChanged((u64, u64), (u64, u64)),
+        /// The validator set changed: the number of validators and the signatures a message needs.
+        SignatureThresholdChanged(u64, u64),
         Txisok(Hash),
         // 交易 = vec<id，签名>
         TranscationVerified(Hash,Vec<(AccountId,Hash)>),
@@ -46,18 +56,25 @@ decl_storage! {
         /// Record the number of signatures per transaction got
         NumberOfSignedContract get(num_of_signed): map T::Hash => u64;
 
-        /// 需要这些数量的签名，才发送这个交易通过的事件
-        /// These amount of signatures are needed to send the event that the transaction verified.
+        /// 需要的签名数量的下限
+        /// Least number of signatures a message needs, whatever the validator set.
         MinNumOfSignature get(min_signature)  : u64 = 1;
 
+        /// 需要签名的验证者比例  numerator / denominator
+        /// A message needs the signatures of more than this fraction of the session validators.
+        ThresholdRatio get(threshold_ratio) : (u64, u64) = (2, 3);
+
+        /// 需要这些数量的签名，才发送这个交易通过的事件
+        /// These amount of signatures are needed to send the event that the transaction verified,
+        /// computed from the validators of the session `ThresholdSession`.
+        SignatureThreshold get(signature_threshold) : u64 = 1;
+        ThresholdSession get(threshold_session) : Option<T::BlockNumber>;
+
         //record transaction   Hash => (accountid,sign)
         //IdSignTxList  get(all_list) : map T::Hash => (T::AccountId,T::Hash);
         Record  get(record) : map T::Hash => Vec<(T::AccountId,T::Hash)>;
        // IdSignTxListC  get(all_list_c) : map T::Hash => Vec<T::AccountId>;
 
-        /// 是否有重复的签名   map 交易Hash => 签名Hash列表
-        RepeatPrevent  get(repeat_prevent) : map T::Hash => Vec<T::Hash>;
-
         /// 已经发送过的交易记录  防止重复发送事件
         /// Transaction records that have been sent prevent duplication of events
         AlreadySentTx get(already_sent) : map T::Hash => u64;
@@ -75,15 +92,35 @@ decl_module! {
         /// 设置最小要求签名数量
         /// Set the minimum required number of signatures
         pub  fn set_min_num(origin,new_num: u64) -> Result{
-            let _sender = ensure_signed(origin)?;
+            T::GovernanceOrigin::ensure_origin(origin)?;
             let newmin = new_num;
-            if newmin < 5 {
-                return Err("too small,should bigger than 5");
+            if newmin < 1 {
+                return Err("too small,should not be 0");
             }
+            let previous = Self::min_signature();
             <MinNumOfSignature<T>>::put(newmin) ;
-            Self::deposit_event(RawEvent::SetMinRequreSignatures(newmin));
+            Self::deposit_event(RawEvent::SetMinRequreSignatures(previous, newmin));
+            Self::update_threshold(true);
+            Ok(())
+        }
+
+        /// 设置需要签名的验证者比例
+        /// Set the fraction of the session validators whose signatures a message needs more than.
+        pub fn set_threshold_ratio(origin, numerator: u64, denominator: u64) -> Result {
+            T::GovernanceOrigin::ensure_origin(origin)?;
+            ensure!(denominator > 0 && numerator < denominator, "invalid ratio");
+            let previous = Self::threshold_ratio();
+            <ThresholdRatio<T>>::put((numerator, denominator));
+            Self::deposit_event(RawEvent::ThresholdRatioChanged(previous, (numerator, denominator)));
+            Self::update_threshold(true);
             Ok(())
         }
+
+        /// 新的session重新计算签名数量
+        /// Recompute the threshold when a new session starts, the validators change with it.
+        fn on_finalize(_n: T::BlockNumber) {
+            Self::update_threshold(false);
+        }
     }
 }
 
@@ -93,53 +130,97 @@ impl<T: Trait> Module<T> {
         Ok(())
     }
 
+    /// Signatures `validators` validators need: more than `ThresholdRatio` of them,
+    /// at least `MinNumOfSignature` and at most all of them.
+    pub fn threshold_of(validators: usize) -> u64 {
+        let (numerator, denominator) = Self::threshold_ratio();
+        let validators = validators as u64;
+        let threshold = validators * numerator / denominator.max(1) + 1;
+        threshold.max(Self::min_signature()).min(validators).max(1)
+    }
+
+    /// Recomputes the threshold if it was computed in another session, or if `force`.
+    fn update_threshold(force: bool) -> u64 {
+        let session = <session::Module<T>>::current_index();
+        if !force && Self::threshold_session() == Some(session) {
+            return Self::signature_threshold();
+        }
+        let validators = <session::Module<T>>::validators();
+        let threshold = Self::threshold_of(validators.len());
+        <ThresholdSession<T>>::put(session);
+        if threshold != Self::signature_threshold() {
+            <SignatureThreshold<T>>::put(threshold);
+            Self::deposit_event(RawEvent::SignatureThresholdChanged(validators.len() as u64, threshold));
+        }
+        threshold
+    }
+
+    /// 收集一个验证者的签名，返回是否已经足够
+    /// Adds the signature of a validator to the ones of a message, and tells whether they
+    /// are enough.
+    ///
+    /// The signatures are counted against the validators and threshold of the block the
+    /// signature is added in: signatures of accounts that are no longer validators are
+    /// dropped, and a validator that signed already may sign again to complete a message
+    /// whose remaining signatures meet a threshold lowered since.
+    pub fn collect<S>(signatures: &mut Vec<(T::AccountId, S)>, who: T::AccountId, signature: S) -> rstd::result::Result<bool, &'static str> {
+        let validators = <session::Module<T>>::validators();
+        ensure!(validators.contains(&who), "not validator");
+        let threshold = Self::update_threshold(false) as usize;
+
+        signatures.retain(|(signer, _)| validators.contains(signer));
+        if signatures.iter().any(|(signer, _)| *signer == who) {
+            ensure!(signatures.len() >= threshold, "repeat!");
+        } else {
+            signatures.push((who, signature));
+        }
+        Ok(signatures.len() >= threshold)
+    }
+
+    /// The Ethereum address that signed `hash`, `None` if the signature is invalid.
+    pub fn recover(hash: &[u8; 32], signature: &[u8]) -> Option<H160> {
+        <relayer::Module<T>>::recover(hash, signature)
+    }
+
+    /// 检查消息是否由中继者登记的以太坊地址签名
+    /// Ensures the message was signed by the Ethereum address bound to the relayer.
+    pub fn verify_relayer(who: &T::AccountId, message: &[u8], signature: &[u8]) -> Result {
+        <relayer::Module<T>>::verify(who, message, signature)
+    }
+
     /// 签名并判断如果当前签名数量足够就发送一个事件
        /// Sign and determine if the current number of signatures is sufficient to send an event
     pub  fn check_signature(who: T::AccountId, transcation: T::Hash, sign: T::Hash, message: T::Hash) -> Result{
         //TODO： 判断这个信息发送的人是否是validator     不在这里 已经前置了
         let sender = who;
-        runtime_io::print("111111");
         <txsave<T>>::put({
             let mut xx = Self::tx_save();
             xx.push(transcation.clone());
             xx  }
         );
-        runtime_io::print("222222");
         //查看该交易是否已经存在，没得话添加上去
         if !<NumberOfSignedContract<T>>::exists(transcation) {
             <NumberOfSignedContract<T>>::insert(&transcation,0);
             <AlreadySentTx<T>>::insert(&transcation,0);
         }
 
-        /// 防止签名重复
-        // 防止一个交易的相同签名的是否重复发送
-        let mut repeat_vec = Self::repeat_prevent(transcation);
-        ensure!(!repeat_vec.contains(&sign),"This signature is repeat!");
-        // ensure!(!repeat_vec.iter().find(|&t| t == &sign).is_none(), "Cannot deposit if already in queue.");
-        // 把某个交易hash的一个签名hash保存，以验证后来的是否重复
-        repeat_vec.push(sign.clone());
-        <RepeatPrevent<T>>::insert(transcation.clone(),repeat_vec.clone());
-
         // 防止一个交易被重复发送，已发送过的会有记录
         if 1 == Self::already_sent(transcation){
             return Err("This Transcation already been sent!");
         }
 
         //增加一条记录  包含  交易hash => vec (验证者,签名hash)
+        // 一个验证者只算一个签名 one signature per validator
         let mut stored_vec = Self::record(transcation);
-        stored_vec.push((sender.clone(),sign.clone()));
+        let enough = Self::collect(&mut stored_vec, sender, sign)?;
         <Record<T>>::insert(transcation.clone(),stored_vec.clone());
 
         //TODO:其他验证
         Self::_verify(transcation)?;
 
         // 判断签名数量是否达到指定要求
-        let numofsigned = Self::num_of_signed(&transcation);
-        let newnumofsigned = numofsigned.checked_add(1)
-        .ok_or("Overflow adding a new sign to Tx")?;
-
-        <NumberOfSignedContract<T>>::insert(&transcation,newnumofsigned);
-        if newnumofsigned < Self::min_signature() {
+        <NumberOfSignedContract<T>>::insert(&transcation,stored_vec.len() as u64);
+        if !enough {
             return Err("Not enough signature!");
         }
 
@@ -151,4 +232,169 @@ impl<T: Trait> Module<T> {
         Self::deposit_event(RawEvent::TranscationVerified(transcation,stored_vec));
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::{Blake2Hasher, H256};
+    use runtime_io::with_externalities;
+    use sr_primitives::{
+        testing::{ConvertUintAuthorityId, Digest, DigestItem, Header, UintAuthorityId},
+        traits::{BlakeTwo256, IdentityLookup},
+        BuildStorage,
+    };
+    use support::impl_outer_origin;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<u64>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl consensus::Trait for Test {
+        type Log = DigestItem;
+        type SessionKey = UintAuthorityId;
+        type InherentOfflineReport = ();
+    }
+    impl timestamp::Trait for Test {
+        type Moment = u64;
+        type OnTimestampSet = ();
+    }
+    impl session::Trait for Test {
+        type ConvertAccountIdToSessionKey = ConvertUintAuthorityId;
+        type OnSessionChange = ();
+        type Event = ();
+    }
+    impl relayer::Trait for Test {
+        type Event = ();
+    }
+
+    pub struct EnsureRoot;
+    impl EnsureOrigin<Origin> for EnsureRoot {
+        type Success = ();
+        fn ensure_origin(o: Origin) -> rstd::result::Result<(), &'static str> {
+            let root: Option<system::RawOrigin<u64>> = o.into();
+            match root {
+                Some(system::RawOrigin::Root) => Ok(()),
+                _ => Err("bad origin: expected root"),
+            }
+        }
+    }
+    impl Trait for Test {
+        type Event = ();
+        type GovernanceOrigin = EnsureRoot;
+    }
+    type Signcheck = Module<Test>;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap()
+            .0
+            .into()
+    }
+
+    fn set_validators(validators: Vec<u64>) {
+        <session::Validators<Test>>::put(validators);
+    }
+
+    #[test]
+    fn threshold_is_more_than_two_thirds() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(Signcheck::threshold_of(1), 1);
+            assert_eq!(Signcheck::threshold_of(3), 3);
+            assert_eq!(Signcheck::threshold_of(4), 3);
+            assert_eq!(Signcheck::threshold_of(6), 5);
+        });
+    }
+
+    #[test]
+    fn min_signatures_is_capped_at_the_validators() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(
+                Signcheck::set_min_num(Origin::signed(1), 6),
+                Err("bad origin: expected root")
+            );
+            assert_eq!(
+                Signcheck::set_min_num(system::RawOrigin::Root.into(), 0),
+                Err("too small,should not be 0")
+            );
+            assert_eq!(
+                Signcheck::set_min_num(system::RawOrigin::Root.into(), 6),
+                Ok(())
+            );
+            assert_eq!(Signcheck::threshold_of(3), 3);
+            assert_eq!(Signcheck::threshold_of(6), 6);
+            assert_eq!(Signcheck::threshold_of(7), 6);
+            assert_eq!(Signcheck::threshold_of(9), 7);
+        });
+    }
+
+    #[test]
+    fn threshold_is_computed_once_per_session() {
+        with_externalities(&mut new_test_ext(), || {
+            set_validators(vec![1, 2, 3]);
+            assert_eq!(Signcheck::update_threshold(false), 3);
+
+            // the validators change with the next session
+            set_validators(vec![1, 2, 3, 4, 5, 6]);
+            assert_eq!(Signcheck::update_threshold(false), 3);
+            <session::CurrentIndex<Test>>::put(1);
+            assert_eq!(Signcheck::update_threshold(false), 5);
+            assert_eq!(Signcheck::signature_threshold(), 5);
+            assert_eq!(Signcheck::threshold_session(), Some(1));
+        });
+    }
+
+    #[test]
+    fn collect_counts_validators_only() {
+        with_externalities(&mut new_test_ext(), || {
+            set_validators(vec![1, 2, 3]);
+            let mut signatures = vec![(9, 9u8)];
+            assert_eq!(
+                Signcheck::collect(&mut signatures, 9, 9),
+                Err("not validator")
+            );
+            assert_eq!(Signcheck::collect(&mut signatures, 1, 1), Ok(false));
+            assert_eq!(signatures, vec![(1, 1)]);
+
+            assert_eq!(Signcheck::collect(&mut signatures, 1, 1), Err("repeat!"));
+            assert_eq!(Signcheck::collect(&mut signatures, 2, 2), Ok(false));
+            assert_eq!(Signcheck::collect(&mut signatures, 3, 3), Ok(true));
+        });
+    }
+
+    #[test]
+    fn collect_completes_a_message_after_the_threshold_is_lowered() {
+        with_externalities(&mut new_test_ext(), || {
+            set_validators(vec![1, 2, 3, 4, 5, 6]);
+            let mut signatures = Vec::new();
+            for who in 1..=4 {
+                assert_eq!(Signcheck::collect(&mut signatures, who, who), Ok(false));
+            }
+
+            // 6 * 1 / 2 + 1 = 4 signatures are enough, a validator that signed completes it
+            assert_eq!(
+                Signcheck::set_threshold_ratio(system::RawOrigin::Root.into(), 1, 2),
+                Ok(())
+            );
+            assert_eq!(Signcheck::signature_threshold(), 4);
+            assert_eq!(Signcheck::collect(&mut signatures, 1, 1), Ok(true));
+            assert_eq!(signatures.len(), 4);
+        });
+    }
+}
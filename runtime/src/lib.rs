@@ -22,6 +22,7 @@
 
 use rstd::prelude::*;
 use support::construct_runtime;
+use support::traits::EnsureOrigin;
 use substrate_primitives::u32_trait::{_2, _4};
 use node_primitives::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Index, AuthorityId, Signature, AuthoritySignature,
@@ -43,7 +44,7 @@ use council::{motions as council_motions, voting as council_voting};
 use council::seats as council_seats;
 #[cfg(any(feature = "std", test))]
 use version::NativeVersion;
-use substrate_primitives::OpaqueMetadata;
+use substrate_primitives::{OpaqueMetadata, H160, H256};
 
 #[cfg(any(feature = "std", test))]
 pub use runtime_primitives::BuildStorage;
@@ -51,6 +52,8 @@ pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
 pub use matrix::Call as MatrixCall;
+pub use relayer::Call as RelayerCall;
+pub use relayer::BINDING_PREFIX as RELAYER_BINDING_PREFIX;
 pub use bank::Call as BankCall;
 pub use exchange::Call as ExchangeCall;
 pub use runtime_primitives::{Permill, Perbill};
@@ -67,8 +70,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("node"),
 	impl_name: create_runtime_str!("ladder-node"),
 	authoring_version: 10,
-	spec_version: 72,
-	impl_version: 72,
+	spec_version: 85,
+	impl_version: 83,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -216,8 +219,29 @@ impl matrix::Trait for Runtime {
 	type Event = Event;
 }
 
+impl relayer::Trait for Runtime {
+	type Event = Event;
+}
+
+/// Root, as `sudo` dispatches, or a council motion approved by at least 4 members.
+pub struct EnsureRootOrCouncil;
+
+impl EnsureOrigin<Origin> for EnsureRootOrCouncil {
+	type Success = ();
+	fn ensure_origin(o: Origin) -> rstd::result::Result<(), &'static str> {
+		let root: Option<system::RawOrigin<AccountId>> = o.clone().into();
+		if let Some(system::RawOrigin::Root) = root {
+			return Ok(());
+		}
+		council_motions::EnsureMembers::<_4>::ensure_origin(o)
+			.map(|_| ())
+			.map_err(|_| "bad origin: expected root or a council motion")
+	}
+}
+
 impl signcheck::Trait for Runtime {
 	type Event = Event;
+	type GovernanceOrigin = EnsureRootOrCouncil;
 }
 
 impl bank::Trait for Runtime {
@@ -259,6 +283,7 @@ construct_runtime!(
 		Sudo: sudo,
 		//
 		Matrix: matrix::{Module, Call, Storage, Event<T>},
+		Relayer: relayer::{Module, Call, Storage, Event<T>},
 		Signcheck: signcheck::{Module, Call, Storage, Event<T>},
 		Bank: bank::{Module,Call,Storage,Config<T>,Event<T>},
 		Exchange: exchange::{Module,Call,Storage,Event<T>},
@@ -396,6 +421,30 @@ impl_runtime_apis! {
 			Consensus::authorities().contains(id)
 		}
 
+		fn validators() -> Vec<AccountId> {
+			Session::validators()
+		}
+
+		fn release_payload(message: Hash) -> Option<(Vec<u8>, Vec<u8>)> {
+			Matrix::release_payload(message)
+		}
+
+		fn rollback_payload(message: Hash) -> Option<(Vec<u8>, Vec<u8>)> {
+			Matrix::rollback_payload(message)
+		}
+
+		fn eth_address(account: &AccountId) -> Option<H160> {
+			Relayer::eth_address_of(account)
+		}
+
+		fn validator_addresses() -> Vec<(AccountId, H160)> {
+			Relayer::validator_addresses()
+		}
+
+		fn side_authorities(coin: H256) -> Option<(Vec<H160>, u64)> {
+			Matrix::side_authorities_since(coin).map(|since| (Matrix::side_authorities(coin), since))
+		}
+
 		/// check the accountid has the access to update the exchange rate data or not
 	    fn check_validator(account: &AccountId) -> bool {
 	       Exchange::check_validator(account)
@@ -412,7 +461,137 @@ decl_runtime_apis! {
 	pub trait VendorApi {
 		fn account_nonce(account: &AccountId) -> Nonce;
 		fn is_authority(id: &SessionKey) -> bool;
+		/// validators of the current session, they relay the matrix events in turn
+		fn validators() -> Vec<AccountId>;
+		/// message and concatenated validator signatures of a verified ingress, for `Bridge.release`
+		fn release_payload(message: Hash) -> Option<(Vec<u8>, Vec<u8>)>;
+		/// rollback message and concatenated validator signatures of an ingress not confirmed in time
+		fn rollback_payload(message: Hash) -> Option<(Vec<u8>, Vec<u8>)>;
+		/// ethereum address the account signs relay messages with
+		fn eth_address(account: &AccountId) -> Option<H160>;
+		/// ethereum addresses of the session validators, to compare with `Bridge.getAuthorities`
+		fn validator_addresses() -> Vec<(AccountId, H160)>;
+		/// signers of the Bridge contract of a coin and the side chain block they took effect at
+		fn side_authorities(coin: H256) -> Option<(Vec<H160>, u64)>;
 		fn check_validator(account: &AccountId) -> bool;
 	    fn record_data(account: &AccountId, exchangerate: u64, time: u64);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_io::with_externalities;
+	use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+	use substrate_primitives::{crypto::UncheckedFrom, Blake2Hasher};
+	use support::dispatch::Dispatchable;
+
+	fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+		system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0.into()
+	}
+
+	fn signed() -> Origin {
+		Origin::signed(AccountId::unchecked_from([1; 32]))
+	}
+
+	fn council(members: u32) -> Origin {
+		council_motions::RawOrigin::Members(members).into()
+	}
+
+	fn eth_address(secret: &[u8; 32]) -> H160 {
+		let secp = Secp256k1::new();
+		let public = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(secret).unwrap());
+		H160::from_slice(&runtime_io::keccak_256(&public.serialize_uncompressed()[1..])[12..])
+	}
+
+	fn eth_sign(secret: &[u8; 32], message: &[u8]) -> Vec<u8> {
+		let secp = Secp256k1::new();
+		let hash = Message::from_slice(&Relayer::eth_message_hash(message)).unwrap();
+		let (recovery_id, compact) = secp
+			.sign_recoverable(&hash, &SecretKey::from_slice(secret).unwrap())
+			.serialize_compact();
+		let mut signature = compact.to_vec();
+		signature.push(27 + recovery_id.to_i32() as u8);
+		signature
+	}
+
+	#[test]
+	fn root_or_council_motion_of_four() {
+		assert!(EnsureRootOrCouncil::ensure_origin(Origin::ROOT).is_ok());
+		assert!(EnsureRootOrCouncil::ensure_origin(council(4)).is_ok());
+		assert!(EnsureRootOrCouncil::ensure_origin(council(3)).is_err());
+		assert!(EnsureRootOrCouncil::ensure_origin(signed()).is_err());
+	}
+
+	#[test]
+	fn exchange_set_min_num_needs_root_or_council() {
+		with_externalities(&mut new_test_ext(), || {
+			assert!(Exchange::set_min_num(signed(), 6).is_err());
+			assert!(Exchange::set_min_num(council(3), 6).is_err());
+			assert_eq!(Exchange::min_signature(), 0);
+			assert_eq!(Exchange::set_min_num(Origin::ROOT, 0), Err("too small,should not be 0"));
+
+			assert_eq!(Exchange::set_min_num(council(4), 6), Ok(()));
+			assert_eq!(Exchange::min_signature(), 6);
+			assert_eq!(Exchange::set_min_num(Origin::ROOT, 1), Ok(()));
+			assert_eq!(Exchange::min_signature(), 1);
+		});
+	}
+
+	#[test]
+	fn bank_setters_need_root_or_council() {
+		with_externalities(&mut new_test_ext(), || {
+			let set_length = || BankCall::<Runtime>::set_session_lenth(20);
+			assert!(set_length().dispatch(signed()).is_err());
+			assert!(set_length().dispatch(council(3)).is_err());
+			assert_eq!(set_length().dispatch(council(4)), Ok(()));
+			assert_eq!(Bank::length(), 20);
+
+			let set_session = || BankCall::<Runtime>::set_session_reward_factor(vec![1, 2, 3], vec![1, 2, 3, 4]);
+			assert!(set_session().dispatch(signed()).is_err());
+			assert_eq!(set_session().dispatch(Origin::ROOT), Ok(()));
+			assert_eq!(Bank::reward_session_value(), vec![1, 2, 3]);
+
+			let set_balance = || BankCall::<Runtime>::set_balance_reward_factor(vec![1, 2, 3], vec![1, 2, 3, 4]);
+			assert!(set_balance().dispatch(signed()).is_err());
+			assert_eq!(set_balance().dispatch(council(4)), Ok(()));
+			assert_eq!(Bank::reward_balance_factor(), vec![1, 2, 3, 4]);
+
+			let draw = || BankCall::<Runtime>::draw_reward(AccountId::unchecked_from([2; 32]));
+			assert!(draw().dispatch(signed()).is_err());
+			// past the origin check, the account is not depositing.
+			assert_eq!(draw().dispatch(Origin::ROOT), Err("Cannot draw if not depositing."));
+		});
+	}
+
+	#[test]
+	fn relay_calls_reject_signatures_of_other_keys() {
+		let relayer = AccountId::unchecked_from([1; 32]);
+		let unbound = AccountId::unchecked_from([2; 32]);
+		let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+		t.extend(SessionConfig {
+			validators: vec![relayer.clone(), unbound.clone()],
+			session_length: 10,
+			keys: vec![],
+		}.build_storage().unwrap().0);
+		with_externalities(&mut t.into(), || {
+			assert_eq!(Relayer::set_address(Origin::ROOT, relayer.clone(), eth_address(&[1; 32])), Ok(()));
+			let message = vec![7u8; 212];
+			let calls: Vec<fn(Vec<u8>, Vec<u8>) -> Call> = vec![
+				|message, signature| Call::Bank(BankCall::deposit(message, signature)),
+				|message, signature| Call::Bank(BankCall::withdraw(message, signature)),
+				|message, signature| Call::Matrix(MatrixCall::ingress(message, signature)),
+			];
+			for call in calls {
+				assert_eq!(
+					call(message.clone(), eth_sign(&[2; 32], &message)).dispatch(Origin::signed(relayer.clone())),
+					Err("signature is not from the relayer address")
+				);
+				assert_eq!(
+					call(message.clone(), eth_sign(&[2; 32], &message)).dispatch(Origin::signed(unbound.clone())),
+					Err("relayer has no bound address")
+				);
+			}
+		});
+	}
+}
@@ -6,14 +6,129 @@
 
 use session;
 use balances;
+use signcheck;
+use relayer;
 use rstd::prelude::Vec;
+use substrate_primitives::{H160, H256};
 use runtime_primitives::traits::*;
-use { system::{self, ensure_signed}};
+use { system::{self, ensure_root, ensure_signed}};
 use support::{
-    decl_module, decl_storage, decl_event, StorageMap, dispatch::Result, ensure
+    decl_module, decl_storage, decl_event, StorageMap, StorageValue, dispatch::Result, ensure
 };
 
-pub trait Trait: balances::Trait + session::Trait {
+/// Length of a secp256k1 signature of a relayer: r, s and v.
+pub const SIGNATURE_LENGTH: usize = 65;
+
+/// Length of a transfer message, the ingress `Bridge.release` takes.
+pub const TRANSFER_MESSAGE_LENGTH: usize = 212;
+
+/// Length of an egress message: a transfer message with the hash of the
+/// released ingress transaction before the log position.
+pub const EGRESS_MESSAGE_LENGTH: usize = 244;
+
+/// Length of an authority message without authorities: the coin, the two
+/// counts, the transaction hash and the block number, block hash and log index.
+pub const AUTHORITY_MINIMUM_LENGTH: usize = 168;
+
+/// Length of an authority set message without authorities: the set id and
+/// the minimum number of signatures.
+pub const AUTHORITY_SET_MINIMUM_LENGTH: usize = 64;
+
+/// A number as a big endian uint256.
+fn uint256(number: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&number.to_be_bytes());
+    word
+}
+
+/// An authority set message for `Bridge.setAuths`.
+/// offset  0: 32 bytes :: uint256 - set id
+/// offset 32: 32 bytes :: uint256 - minimum signatures
+/// offset 64: 20 bytes :: address - authority0
+/// offset 84: 20 bytes :: address - authority1
+pub fn authority_set_message(set_id: u64, min_signatures: u64, authorities: &[H160]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(AUTHORITY_SET_MINIMUM_LENGTH + authorities.len() * 20);
+    message.extend_from_slice(&uint256(set_id));
+    message.extend_from_slice(&uint256(min_signatures));
+    for authority in authorities {
+        message.extend_from_slice(&authority[..]);
+    }
+    message
+}
+
+/// A `ReplaceAuths` log of a side chain Bridge contract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorityMessage {
+    pub coin: H256,
+    pub last: Vec<H160>,
+    pub next: Vec<H160>,
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub log_index: u64,
+}
+
+impl AuthorityMessage {
+    /// offset 0: 32 bytes :: bytes32 - coin
+    /// offset 32: 4 bytes :: u32 little endian - number of last authorities
+    /// offset 36: 20 bytes each :: address - last authorities
+    /// offset a: 4 bytes :: u32 little endian - number of next authorities
+    /// offset a+4: 20 bytes each :: address - next authorities
+    /// offset b: 32 bytes :: bytes32 - transaction hash
+    /// offset b+32: 96 bytes :: block number, block hash and log index
+    pub fn decode(message: &[u8]) -> Option<Self> {
+        if message.len() < AUTHORITY_MINIMUM_LENGTH {
+            return None;
+        }
+        let mut input = message;
+        let coin = H256::from_slice(Self::take(&mut input, 32)?);
+        let last = Self::take_addresses(&mut input)?;
+        let next = Self::take_addresses(&mut input)?;
+        let tx_hash = H256::from_slice(Self::take(&mut input, 32)?);
+        let block_number = Self::take_u64(&mut input)?;
+        let block_hash = H256::from_slice(Self::take(&mut input, 32)?);
+        let log_index = Self::take_u64(&mut input)?;
+        if !input.is_empty() {
+            return None;
+        }
+        Some(AuthorityMessage { coin, last, next, tx_hash, block_number, block_hash, log_index })
+    }
+
+    fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if input.len() < len {
+            return None;
+        }
+        let (head, tail) = input.split_at(len);
+        *input = tail;
+        Some(head)
+    }
+
+    fn take_addresses(input: &mut &[u8]) -> Option<Vec<H160>> {
+        let count = Self::take(input, 4)?;
+        let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        if count > input.len() / 20 {
+            return None;
+        }
+        let mut addresses = Vec::with_capacity(count);
+        for _ in 0..count {
+            addresses.push(H160::from_slice(Self::take(input, 20)?));
+        }
+        Some(addresses)
+    }
+
+    /// A big endian uint256 that fits in an u64.
+    fn take_u64(input: &mut &[u8]) -> Option<u64> {
+        let word = Self::take(input, 32)?;
+        if word[..24].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let mut number = [0u8; 8];
+        number.copy_from_slice(&word[24..]);
+        Some(u64::from_be_bytes(number))
+    }
+}
+
+pub trait Trait: balances::Trait + session::Trait + signcheck::Trait {
     /// The overarching event type.
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
 }
@@ -27,47 +142,83 @@ decl_module! {
         //  offset 32: 20 bytes :: address - recipient address
         //  offset 52: 32 bytes :: uint256 - value
         //  offset 84: 32 bytes :: bytes32 - transaction hash
+        //  offset 116: 32 bytes :: uint256 - block number
+        //  offset 148: 32 bytes :: bytes32 - block hash
+        //  offset 180: 32 bytes :: uint256 - log index
+        /// The message is keyed by its hash, which covers the position of the
+        /// source log, so two events of one transaction are relayed apart.
+        /// Each validator signs the message with the Ethereum address bound to it
+        /// in `relayer`, once enough of them did the signatures are concatenated
+        /// for `Bridge.release`.
         pub fn ingress(origin, message: Vec<u8>, signature: Vec<u8>) -> Result {
             let sender = ensure_signed(origin)?;
+            ensure!(signature.len() == SIGNATURE_LENGTH, "invalid signature length");
+            <signcheck::Module<T>>::verify_relayer(&sender, &message, &signature)?;
             let hash = T::Hashing::hash_of(&message);
-
-            let signature_hash = T::Hashing::hash_of(&signature);
-            if let Ok(()) = Self::verify_ingress_message(sender,hash,signature_hash ) {
-
-                Self::deposit_event(RawEvent::Ingress(signature.clone(), message.clone()));
-                <IngressOf<T>>::insert(hash, message.clone());
-                return  Ok(());
-            }
-            Err("ingress err")
+            Self::verify_ingress_message(sender, hash, message, signature)
         }
 
         /// Data Forwarding Confirmation Message
         pub fn egress(origin, message: Vec<u8>, signature: Vec<u8>) -> Result {
             let sender = ensure_signed(origin)?;
+            ensure!(signature.len() == SIGNATURE_LENGTH, "invalid signature length");
+            <signcheck::Module<T>>::verify_relayer(&sender, &message, &signature)?;
             let hash = T::Hashing::hash_of(&message);
-            let signature_hash = T::Hashing::hash_of(&signature);
-            if let Ok(()) = Self::verify_egress_message(sender,hash,signature_hash ) {
-
-                Self::deposit_event(RawEvent::Egress(signature.clone(), message.clone()));
-                <EgressOf<T>>::insert(hash, message.clone());
-                 return  Ok(());
-            }
-             Err("egress err")
+            Self::verify_egress_message(sender, hash, message, signature)
         }
 
         /// Data Forwarding Timeout Return Message
-        pub fn rollback(_origin, _message: Vec<u8>, _signature: Vec<u8>) -> Result {
+        /// offset   0: 212 bytes :: bytes - the ingress message
+        /// offset 212: 32 bytes :: uint256 - block the rollback became due at
+        /// An ingress whose egress is not verified within `RollbackTimeout` blocks
+        /// becomes due for rollback, each validator signs the rollback message and
+        /// once enough of them did, the value can be returned on the source chain.
+        pub fn rollback(origin, message: Vec<u8>, signature: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(signature.len() == SIGNATURE_LENGTH, "invalid signature length");
+            <signcheck::Module<T>>::verify_relayer(&sender, &message, &signature)?;
+            let hash = T::Hashing::hash_of(&message);
+            Self::verify_rollback_message(sender, hash, signature)
+        }
+
+        /// 设置回滚超时的区块数
+        /// Set the number of blocks an ingress waits for its egress before it is rolled back.
+        pub fn set_rollback_timeout(origin, blocks: T::BlockNumber) -> Result {
+            ensure_root(origin)?;
+            ensure!(!blocks.is_zero(), "rollback timeout must not be zero");
+            <RollbackTimeout<T>>::put(blocks);
             Ok(())
         }
 
+        fn on_finalize(n: T::BlockNumber) {
+            Self::expire_transfers(n);
+            Self::update_authority_set();
+        }
+
 
         /// Resetting Validation Node Messages
-        /// offset 0: 32 bytes :: uint256 - block number
-        /// offset 32: 20 bytes :: address - authority0
-        /// offset 52: 20 bytes :: address - authority1
-        /// offset 72: 20 bytes :: ..    ....
-        pub fn reset_authorities(_origin, _message: Vec<u8>, _signature: Vec<u8>) -> Result {
-            Ok(())
+        /// A `ReplaceAuths` log of a side chain, laid out as `AuthorityMessage`.
+        /// Once enough validators signed it, the next authorities are the signers
+        /// of the Bridge contract of the coin from the block of the log.
+        pub fn reset_authorities(origin, message: Vec<u8>, signature: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(signature.len() == SIGNATURE_LENGTH, "invalid signature length");
+            <signcheck::Module<T>>::verify_relayer(&sender, &message, &signature)?;
+            let authorities = AuthorityMessage::decode(&message).ok_or("invalid authority message")?;
+            let hash = T::Hashing::hash_of(&message);
+            Self::verify_authority_message(sender, hash, authorities, signature)
+        }
+
+        /// Authority Set Message for the side chains
+        /// The runtime builds the message, laid out as `authority_set_message`, when
+        /// the validators of a new session bound other Ethereum addresses, and emits
+        /// `AuthoritySetDue`. Each validator signs it, once enough of them did it is
+        /// sent to `setAuths` of every side chain with the concatenated signatures.
+        pub fn sign_authorities(origin, message: Vec<u8>, signature: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            ensure!(signature.len() == SIGNATURE_LENGTH, "invalid signature length");
+            <signcheck::Module<T>>::verify_relayer(&sender, &message, &signature)?;
+            Self::verify_authority_set(sender, message, signature)
         }
 
 
@@ -79,9 +230,7 @@ decl_storage! {
     trait Store for Module<T: Trait> as Matrix {
         //pub IngressOf get(ingress_of): map T::Hash => Vec<u8>;
 
-        /// ingress & egress
-        MinNumberOfSignatureLimit get(min_signature_limit): u64;
-
+        // ingress & egress, `signcheck` tells how many signatures they need
         //记录每个交易的签名的数量
         NumberOfSignedIngressTx get(number_of_signed_ingress): map T::Hash => u64;
         //已经发送过的交易记录  防止重复发送事件
@@ -92,6 +241,10 @@ decl_storage! {
         IngressList  get(ingress_list) : map T::Hash => Vec<(T::AccountId,T::Hash)>;
         //
         IngressOf  get(ingress_of) :  map T::Hash  => Vec<u8> ;
+        /// Signatures of each validator for a message, until there are enough of them.
+        IngressSignatures get(ingress_signatures) : map T::Hash => Vec<(T::AccountId, Vec<u8>)>;
+        /// Signatures of a verified message for `Bridge.release`, concatenated in validator order.
+        IngressRelease get(ingress_release) : map T::Hash => Option<Vec<u8>>;
 
         //记录每个交易的签名的数量
         NumberOfSignedEgressTx get(number_of_signed_egress): map T::Hash => u64;
@@ -103,7 +256,58 @@ decl_storage! {
         EgressList  get(egress_list) : map T::Hash => Vec<(T::AccountId,T::Hash)>;
         //
         EgressOf  get(egress_of) : map T::Hash => Vec<u8>;
-
+        /// Signatures of each validator for a message, until there are enough of them.
+        EgressSignatures get(egress_signatures) : map T::Hash => Vec<(T::AccountId, Vec<u8>)>;
+        /// Signatures of a verified message, concatenated in validator order.
+        EgressRelease get(egress_release) : map T::Hash => Option<Vec<u8>>;
+
+        /// 回滚超时的区块数
+        /// Blocks an ingress waits for its egress before it is due for rollback.
+        RollbackTimeout get(rollback_timeout) : T::BlockNumber = T::BlockNumber::sa(14400);
+        /// Hash of the ingress message and block of the rollback of transfers waiting for their
+        /// egress, by transfer.
+        PendingTransfers get(pending_transfer) : map T::Hash => Option<(T::Hash, T::BlockNumber)>;
+        /// Transfers without a verified egress, by their recipient, value and source transaction,
+        /// in the order their ingress was verified.
+        UnreleasedTransfers get(unreleased_transfers) : map T::Hash => Vec<T::Hash>;
+        /// Transfers that are due for rollback at a block, unless their egress was verified.
+        TransferDeadlines get(transfer_deadlines) : map T::BlockNumber => Vec<T::Hash>;
+        /// Rollback messages due, by their hash, until enough validators signed them.
+        RollbackOf get(rollback_of) : map T::Hash => Option<Vec<u8>>;
+        /// Hash of the rollback message of a transfer that is due for rollback.
+        RollbackOfTransfer get(rollback_of_transfer) : map T::Hash => Option<T::Hash>;
+        /// Signatures of each validator for a rollback message, until there are enough of them.
+        RollbackSignatures get(rollback_signatures) : map T::Hash => Vec<(T::AccountId, Vec<u8>)>;
+        /// Signatures of a verified rollback message, concatenated in validator order.
+        RollbackRelease get(rollback_release) : map T::Hash => Option<Vec<u8>>;
+        /// Verified rollback messages, by their hash.
+        RollbackMessage get(rollback_message) : map T::Hash => Vec<u8>;
+        /// 已经回滚的转账
+        /// Hash of the verified rollback message of each transfer that was rolled back.
+        RolledBackTransfers get(rolled_back_transfer) : map T::Hash => Option<T::Hash>;
+
+        /// 已经生效的验证节点更换消息
+        /// Authority messages that took effect.
+        AlreadyResetAuthorities get(already_reset_authorities) : map T::Hash => bool;
+        /// Signatures of each validator for an authority message, until there are enough of them.
+        AuthoritySignatures get(authority_signatures) : map T::Hash => Vec<(T::AccountId, Vec<u8>)>;
+        /// 侧链合约当前的验证节点
+        /// Signers of the Bridge contract of each coin.
+        SideAuthorities get(side_authorities) : map H256 => Vec<H160>;
+        /// Side chain block the signers of each coin took effect at.
+        SideAuthoritiesSince get(side_authorities_since) : map H256 => Option<u64>;
+
+        /// 最近一次生成的验证节点集合
+        /// Id of the last authority set built for the side chains.
+        AuthoritySetId get(authority_set_id) : u64;
+        /// Addresses of the last authority set built.
+        AuthoritySetAddresses get(authority_set_addresses) : Vec<H160>;
+        /// Session whose validators the authority set was last checked against.
+        AuthoritySetSession get(authority_set_session) : Option<T::BlockNumber>;
+        /// The authority set message waiting for signatures.
+        PendingAuthoritySet get(pending_authority_set) : Option<Vec<u8>>;
+        /// Signatures of each validator for the pending authority set, until there are enough of them.
+        AuthoritySetSignatures get(authority_set_signatures) : Vec<(T::AccountId, Vec<u8>)>;
 
     }
 }
@@ -116,13 +320,31 @@ decl_event! {
         <T as system::Trait>::Hash,
         <T as system::Trait>::BlockNumber
     {
+        /// A verified message and the concatenated signatures of the validators,
+        /// the arguments of `Bridge.release` as they are.
         Ingress(Vec<u8>, Vec<u8>),
+        /// A verified message and the concatenated signatures of the validators.
         Egress(Vec<u8>, Vec<u8>),
+        /// An ingress was not confirmed in time, validators sign this rollback message.
+        RollbackDue(Vec<u8>),
+        /// A verified rollback message and the concatenated signatures of the validators.
+        Rollback(Vec<u8>, Vec<u8>),
+        /// The egress of a transfer was verified after its rollback, the value was returned
+        /// and released: the egress message and the rollback message.
+        TransferConflict(Vec<u8>, Vec<u8>),
 
           // 交易 = vec<id，签名>
         IngressVerified(Hash,Vec<(AccountId,Hash)>),
         EgressVerified(Hash,Vec<(AccountId,Hash)>),
 
+        /// A new authority set for the side chains, validators sign this message.
+        AuthoritySetDue(Vec<u8>),
+        /// An authority set message and its signatures for `Bridge.setAuths`.
+        SetAuthorities(Vec<u8>, Vec<u8>),
+        /// The signers of the Bridge contract of a coin were replaced at a side chain
+        /// block: the hash of the message, the coin, the new signers and the block.
+        AuthoritiesReset(Hash, H256, Vec<H160>, u64),
+
         Has(Hash),
 
         //bank moduel
@@ -138,77 +360,589 @@ decl_event! {
 
 impl<T: Trait> Module<T>
 {
+    /// The signatures concatenated in the order of the session validators.
+    fn concat_signatures(mut signatures: Vec<(T::AccountId, Vec<u8>)>) -> Vec<u8> {
+        let validator_set = <session::Module<T>>::validators();
+        signatures.sort_by_key(|(signer, _)| {
+            validator_set.iter().position(|validator| validator == signer).unwrap_or(validator_set.len())
+        });
+        signatures.into_iter().flat_map(|(_, signature)| signature).collect()
+    }
 
-    /// 数据转发请求消息 ingress
-    /// Data Forwarding Confirmation Message
-    fn verify_ingress_message(sender: T::AccountId, message: T::Hash, signature: T::Hash) -> Result{
+    /// The message of a verified ingress and the signatures for `Bridge.release`,
+    /// `None` once the transfer is due for rollback.
+    pub fn release_payload(message: T::Hash) -> Option<(Vec<u8>, Vec<u8>)> {
+        Self::ingress_release(message).map(|signatures| (Self::ingress_of(message), signatures))
+    }
 
-        //是否在验证者集合中
-        let validator_set = <session::Module<T>>::validators();
-        ensure!(!validator_set.contains(&sender),"not validator");
+    /// A verified rollback message and the signatures of the validators.
+    pub fn rollback_payload(message: T::Hash) -> Option<(Vec<u8>, Vec<u8>)> {
+        Self::rollback_release(message).map(|signatures| (Self::rollback_message(message), signatures))
+    }
+
+    /// A transfer is its recipient, value, source transaction and source log index, so the
+    /// transfers of one transaction are told apart. `message` starts with an ingress message.
+    fn transfer_key(message: &[u8]) -> T::Hash {
+        let mut transfer = message[32..116].to_vec();
+        transfer.extend_from_slice(&message[180..212]);
+        T::Hashing::hash(&transfer)
+    }
 
-        //查看该交易是否存在，没得话添加上去
-        if !<NumberOfSignedIngressTx<T>>::exists(message) {
-            <NumberOfSignedIngressTx<T>>::insert(&message,0);
-            <AlreadySentIngressTx<T>>::insert(&message,0);
+    /// The recipient, value and source transaction of a transfer, which both its ingress
+    /// and its egress carry. The `Egress` log has no source log index, so the egresses of
+    /// equal transfers of one transaction release them in the order they were verified.
+    fn transfer_source(recipient_value: &[u8], source_hash: &[u8]) -> T::Hash {
+        let mut source = recipient_value.to_vec();
+        source.extend_from_slice(source_hash);
+        T::Hashing::hash(&source)
+    }
+
+    /// Waits for the egress of a verified ingress until the rollback timeout.
+    fn track_transfer(hash: T::Hash, message: &[u8]) {
+        if message.len() != TRANSFER_MESSAGE_LENGTH {
+            return;
         }
+        let key = Self::transfer_key(message);
+        let deadline = <system::Module<T>>::block_number() + Self::rollback_timeout();
+        <PendingTransfers<T>>::insert(key, (hash, deadline));
+        let source = Self::transfer_source(&message[32..84], &message[84..116]);
+        <UnreleasedTransfers<T>>::mutate(source, |keys| keys.push(key));
+        <TransferDeadlines<T>>::mutate(deadline, |keys| keys.push(key));
+    }
 
-        //查看这个签名的是否重复发送交易 重复发送就
-        let mut repeat_vec = Self::ingress_signed_sender(&message);
-        ensure!(repeat_vec.contains(&sender),"repeat!");
+    /// The egress of a transfer was verified, it is not rolled back. A rollback still
+    /// collecting signatures is cancelled, a verified one is reported as a conflict.
+    fn confirm_transfer(message: &[u8]) {
+        if message.len() != EGRESS_MESSAGE_LENGTH {
+            return;
+        }
+        // 优先释放还没回滚的转账 a transfer not rolled back is released first
+        let source = Self::transfer_source(&message[32..84], &message[116..148]);
+        let mut keys = Self::unreleased_transfers(source);
+        if keys.is_empty() {
+            return;
+        }
+        let index = keys.iter().position(|key| Self::rolled_back_transfer(key).is_none()).unwrap_or(0);
+        let key = keys.remove(index);
+        if keys.is_empty() {
+            <UnreleasedTransfers<T>>::remove(source);
+        } else {
+            <UnreleasedTransfers<T>>::insert(source, keys);
+        }
+        <PendingTransfers<T>>::remove(key);
+        // 回滚还没签完就取消 a rollback not signed yet is cancelled
+        if let Some(rollback) = <RollbackOfTransfer<T>>::take(key) {
+            <RollbackOf<T>>::remove(rollback);
+            <RollbackSignatures<T>>::remove(rollback);
+        }
+        // 已经回滚的转账又被释放 the transfer was both returned and released
+        if let Some(rollback) = Self::rolled_back_transfer(key) {
+            Self::deposit_event(RawEvent::TransferConflict(message.to_vec(), Self::rollback_message(rollback)));
+        }
+    }
 
-        //查看交易是否已被发送
-        if 1 == Self::already_sent_ingress(&message){
-            return Err("has been sent");
+    /// The transfers whose egress was not verified by block `n` are due for rollback,
+    /// their signatures for `Bridge.release` are dropped.
+    fn expire_transfers(n: T::BlockNumber) {
+        for key in <TransferDeadlines<T>>::take(n) {
+            let (ingress, deadline) = match Self::pending_transfer(key) {
+                Some(pending) => pending,
+                None => continue,
+            };
+            if deadline != n {
+                continue;
+            }
+            <PendingTransfers<T>>::remove(key);
+            <IngressRelease<T>>::remove(ingress);
+
+            let mut message = Self::ingress_of(ingress);
+            message.extend_from_slice(&uint256(n.as_()));
+            let hash = T::Hashing::hash_of(&message);
+            <RollbackOf<T>>::insert(hash, message.clone());
+            <RollbackOfTransfer<T>>::insert(key, hash);
+            Self::deposit_event(RawEvent::RollbackDue(message));
         }
+    }
+
+    /// 数据转发请求消息 ingress
+    /// Data Forwarding Request Message
+    fn verify_ingress_message(sender: T::AccountId, hash: T::Hash, message: Vec<u8>, signature: Vec<u8>) -> Result{
+
+        //是否在验证者集合中
+        //查看交易是否已被发送
+        ensure!(Self::already_sent_ingress(&hash) == 0, "has been sent");
+
+        //是否在验证者集合中, 重复签名  signcheck counts one signature per validator
+        let mut signatures = Self::ingress_signatures(&hash);
+        let enough = <signcheck::Module<T>>::collect(&mut signatures, sender, signature)?;
 
         //增加一条记录 ->  交易 = vec of 验证者 签名
-        let mut stored_vec = Self::ingress_list(&message);
-        stored_vec.push((sender.clone(), signature.clone()));
-        <IngressList<T>>::insert(message.clone(), stored_vec.clone());
+        let stored_vec: Vec<(T::AccountId, T::Hash)> = signatures.iter()
+            .map(|(signer, signature)| (signer.clone(), T::Hashing::hash_of(signature)))
+            .collect();
+        <IngressList<T>>::insert(hash, stored_vec.clone());
         //更新重复记录
-        repeat_vec.push(sender.clone());
-        <IngressSignedSender<T>>::insert(message.clone(),repeat_vec.clone());
+        let signers: Vec<T::AccountId> = signatures.iter().map(|(signer, _)| signer.clone()).collect();
+        <IngressSignedSender<T>>::insert(hash, signers);
+        <NumberOfSignedIngressTx<T>>::insert(hash, signatures.len() as u64);
 
-        //记录已经发送过的交易  同时发送事件Event
-        <AlreadySentIngressTx<T>>::insert(&message,1);
-        Self::deposit_event(RawEvent::IngressVerified(message,stored_vec));
+        if !enough {
+            <IngressSignatures<T>>::insert(hash, signatures);
+            return Ok(());
+        }
+
+        //签名足够  记录已经发送过的交易  同时发送事件Event
+        let release = Self::concat_signatures(signatures);
+        <IngressSignatures<T>>::remove(hash);
+        <AlreadySentIngressTx<T>>::insert(hash, 1);
+        <IngressOf<T>>::insert(hash, message.clone());
+        <IngressRelease<T>>::insert(hash, release.clone());
+        Self::track_transfer(hash, &message);
+        Self::deposit_event(RawEvent::IngressVerified(hash, stored_vec));
+        Self::deposit_event(RawEvent::Ingress(message, release));
         Ok(())
     }
 
-    /// 数据转发确认消息 egress
-    /// Data Forwarding Confirmation Message
-    fn verify_egress_message(sender: T::AccountId, message: T::Hash, signature: T::Hash) -> Result{
-        //是否在验证者集合中
-        let validator_set = <session::Module<T>>::validators();
-        ensure!(!validator_set.contains(&sender),"not validator");
+    /// 验证节点更换消息
+    /// Resetting Validation Node Messages
+    fn verify_authority_message(sender: T::AccountId, hash: T::Hash, message: AuthorityMessage, signature: Vec<u8>) -> Result {
+        ensure!(!Self::already_reset_authorities(&hash), "has been reset");
 
-        //查看该交易是否存在，没得话添加上去
-        if !<NumberOfSignedEgressTx<T>>::exists(message) {
-            <NumberOfSignedEgressTx<T>>::insert(&message,0);
-            <AlreadySentEgressTx<T>>::insert(&message,0);
+        // 只接受比当前更新的更换 a replacement older than the current signers is stale
+        if let Some(since) = Self::side_authorities_since(&message.coin) {
+            ensure!(message.block_number >= since, "authorities are outdated");
+            ensure!(message.last == Self::side_authorities(&message.coin), "last authorities do not match");
         }
 
-        //查看这个签名的是否重复发送交易 重复发送就滚粗
-        let mut repeat_vec = Self::egress_signed_sender(&message);
-        ensure!(repeat_vec.contains(&sender),"repeat!");
+        let mut signatures = Self::authority_signatures(&hash);
+        if !<signcheck::Module<T>>::collect(&mut signatures, sender, signature)? {
+            <AuthoritySignatures<T>>::insert(hash, signatures);
+            return Ok(());
+        }
 
-        //查看交易是否已被发送
-        if 1 == Self::already_sent_egress(&message){
-            return Err("has been sent");
+        <AuthoritySignatures<T>>::remove(hash);
+        <AlreadyResetAuthorities<T>>::insert(hash, true);
+        <SideAuthorities<T>>::insert(message.coin, message.next.clone());
+        <SideAuthoritiesSince<T>>::insert(message.coin, message.block_number);
+        Self::deposit_event(RawEvent::AuthoritiesReset(hash, message.coin, message.next, message.block_number));
+        Ok(())
+    }
+
+    /// 验证者变化时生成新的验证节点集合
+    /// Builds a new authority set once per session, if the validators of the session
+    /// bound other Ethereum addresses than the last set. Validators without an address
+    /// are left out, an address bound later joins at the next session.
+    fn update_authority_set() {
+        let session = <session::Module<T>>::current_index();
+        if Self::authority_set_session() == Some(session) {
+            return;
+        }
+        <AuthoritySetSession<T>>::put(session);
+
+        let addresses: Vec<H160> = <relayer::Module<T>>::validator_addresses()
+            .into_iter()
+            .map(|(_, address)| address)
+            .collect();
+        if addresses.is_empty() || addresses == Self::authority_set_addresses() {
+            return;
+        }
+        let set_id = Self::authority_set_id() + 1;
+        let min_signatures = <signcheck::Module<T>>::threshold_of(addresses.len());
+        let message = authority_set_message(set_id, min_signatures, &addresses);
+
+        // 新的集合取代还没签完的旧集合 a newer set replaces one still collecting signatures
+        <AuthoritySetSignatures<T>>::kill();
+        <AuthoritySetId<T>>::put(set_id);
+        <AuthoritySetAddresses<T>>::put(addresses);
+        <PendingAuthoritySet<T>>::put(message.clone());
+        Self::deposit_event(RawEvent::AuthoritySetDue(message));
+    }
+
+    /// 验证节点集合消息
+    /// Authority Set Message
+    fn verify_authority_set(sender: T::AccountId, message: Vec<u8>, signature: Vec<u8>) -> Result {
+        ensure!(Self::pending_authority_set().as_ref() == Some(&message), "authority set is not due");
+
+        let mut signatures = Self::authority_set_signatures();
+        if !<signcheck::Module<T>>::collect(&mut signatures, sender, signature)? {
+            <AuthoritySetSignatures<T>>::put(signatures);
+            return Ok(());
         }
 
+        let release = Self::concat_signatures(signatures);
+        <AuthoritySetSignatures<T>>::kill();
+        <PendingAuthoritySet<T>>::kill();
+        Self::deposit_event(RawEvent::SetAuthorities(message, release));
+        Ok(())
+    }
+
+    /// 数据转发超时回滚消息 rollback
+    /// Data Forwarding Timeout Return Message
+    fn verify_rollback_message(sender: T::AccountId, hash: T::Hash, signature: Vec<u8>) -> Result {
+        let message = Self::rollback_of(&hash).ok_or("rollback is not due")?;
+
+        let mut signatures = Self::rollback_signatures(&hash);
+        if !<signcheck::Module<T>>::collect(&mut signatures, sender, signature)? {
+            <RollbackSignatures<T>>::insert(hash, signatures);
+            return Ok(());
+        }
+
+        let release = Self::concat_signatures(signatures);
+        let key = Self::transfer_key(&message);
+        <RollbackSignatures<T>>::remove(hash);
+        <RollbackOf<T>>::remove(hash);
+        <RollbackOfTransfer<T>>::remove(key);
+        <RolledBackTransfers<T>>::insert(key, hash);
+        <RollbackMessage<T>>::insert(hash, message.clone());
+        <RollbackRelease<T>>::insert(hash, release.clone());
+        Self::deposit_event(RawEvent::Rollback(message, release));
+        Ok(())
+    }
+
+    /// 数据转发确认消息 egress
+    /// Data Forwarding Confirmation Message
+    fn verify_egress_message(sender: T::AccountId, hash: T::Hash, message: Vec<u8>, signature: Vec<u8>) -> Result{
+        //是否在验证者集合中
+        //查看交易是否已被发送
+        ensure!(Self::already_sent_egress(&hash) == 0, "has been sent");
+
+        //是否在验证者集合中, 重复签名  signcheck counts one signature per validator
+        let mut signatures = Self::egress_signatures(&hash);
+        let enough = <signcheck::Module<T>>::collect(&mut signatures, sender, signature)?;
+
         //增加一条记录 ->  交易 = vec of 验证者 签名
-        let mut stored_vec = Self::egress_list(&message);
-        stored_vec.push((sender.clone(), signature.clone()));
-        <EgressList<T>>::insert(message.clone(), stored_vec.clone());
+        let stored_vec: Vec<(T::AccountId, T::Hash)> = signatures.iter()
+            .map(|(signer, signature)| (signer.clone(), T::Hashing::hash_of(signature)))
+            .collect();
+        <EgressList<T>>::insert(hash, stored_vec.clone());
         //更新重复记录
-        repeat_vec.push(sender.clone());
-        <EgressSignedSender<T>>::insert(message.clone(),repeat_vec.clone());
+        let signers: Vec<T::AccountId> = signatures.iter().map(|(signer, _)| signer.clone()).collect();
+        <EgressSignedSender<T>>::insert(hash, signers);
+        <NumberOfSignedEgressTx<T>>::insert(hash, signatures.len() as u64);
 
-        //记录已经发送过的交易  同时发送事件Event
-        <AlreadySentEgressTx<T>>::insert(&message,1);
-        Self::deposit_event(RawEvent::EgressVerified(message,stored_vec));
+        if !enough {
+            <EgressSignatures<T>>::insert(hash, signatures);
+            return Ok(());
+        }
+
+        //签名足够  记录已经发送过的交易  同时发送事件Event
+        let release = Self::concat_signatures(signatures);
+        <EgressSignatures<T>>::remove(hash);
+        <AlreadySentEgressTx<T>>::insert(hash, 1);
+        <EgressOf<T>>::insert(hash, message.clone());
+        <EgressRelease<T>>::insert(hash, release.clone());
+        Self::confirm_transfer(&message);
+        Self::deposit_event(RawEvent::EgressVerified(hash, stored_vec));
+        Self::deposit_event(RawEvent::Egress(message, release));
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Origin, Relayer, Runtime, SessionConfig};
+    use node_primitives::AccountId;
+    use runtime_io::with_externalities;
+    use runtime_primitives::BuildStorage;
+    use substrate_primitives::{crypto::UncheckedFrom, Blake2Hasher};
+
+    type Matrix = Module<Runtime>;
+    type System = system::Module<Runtime>;
+
+    fn validator(n: u8) -> AccountId {
+        AccountId::unchecked_from([n; 32])
+    }
+
+    /// Three validators, so a message needs all three signatures.
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::<Runtime>::default().build_storage().unwrap().0;
+        t.extend(SessionConfig {
+            validators: vec![validator(1), validator(2), validator(3)],
+            session_length: 10,
+            keys: vec![],
+        }.build_storage().unwrap().0);
+        t.into()
+    }
+
+    fn ingress_message(log_index: u64) -> Vec<u8> {
+        let mut message = vec![1u8; 32]; // tag
+        message.extend_from_slice(&[2u8; 20]); // recipient
+        message.extend_from_slice(&uint256(100)); // value
+        message.extend_from_slice(&[3u8; 32]); // source transaction
+        message.extend_from_slice(&uint256(7)); // block number
+        message.extend_from_slice(&[4u8; 32]); // block hash
+        message.extend_from_slice(&uint256(log_index)); // log index
+        assert_eq!(message.len(), TRANSFER_MESSAGE_LENGTH);
+        message
+    }
+
+    fn egress_message(log_index: u64) -> Vec<u8> {
+        let mut message = ingress_message(0)[..84].to_vec();
+        message.extend_from_slice(&[5u8; 32]); // release transaction
+        message.extend_from_slice(&[3u8; 32]); // released ingress transaction
+        message.extend_from_slice(&uint256(9)); // block number
+        message.extend_from_slice(&[6u8; 32]); // block hash
+        message.extend_from_slice(&uint256(log_index)); // log index
+        assert_eq!(message.len(), EGRESS_MESSAGE_LENGTH);
+        message
+    }
+
+    fn hash(message: &Vec<u8>) -> <Runtime as system::Trait>::Hash {
+        <Runtime as system::Trait>::Hashing::hash_of(message)
+    }
+
+    /// The ingress is verified at block 1 and due for rollback at block 11.
+    fn verify_ingress(log_index: u64) -> <Runtime as system::Trait>::Hash {
+        System::set_block_number(1);
+        <RollbackTimeout<Runtime>>::put(10);
+        let message = ingress_message(log_index);
+        for n in 1..=3 {
+            Matrix::verify_ingress_message(validator(n), hash(&message), message.clone(), vec![n]).unwrap();
+        }
+        assert!(Matrix::release_payload(hash(&message)).is_some());
+        hash(&message)
+    }
+
+    fn verify_egress(log_index: u64) {
+        let message = egress_message(log_index);
+        for n in 1..=3 {
+            Matrix::verify_egress_message(validator(n), hash(&message), message.clone(), vec![n]).unwrap();
+        }
+    }
+
+    fn rollback_message(log_index: u64) -> Vec<u8> {
+        let mut message = ingress_message(log_index);
+        message.extend_from_slice(&uint256(11));
+        message
+    }
+
+    fn has_conflict() -> bool {
+        System::events().iter().any(|record| match record.event {
+            Event::matrix(RawEvent::TransferConflict(..)) => true,
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn egress_before_the_rollback_is_due() {
+        with_externalities(&mut new_test_ext(), || {
+            let ingress = verify_ingress(0);
+            verify_egress(1);
+            Matrix::expire_transfers(11);
+
+            let rollback = hash(&rollback_message(0));
+            assert_eq!(Matrix::rollback_of(rollback), None);
+            assert!(Matrix::verify_rollback_message(validator(1), rollback, vec![1]).is_err());
+            assert!(Matrix::release_payload(ingress).is_some());
+            assert!(!has_conflict());
+        });
+    }
+
+    #[test]
+    fn egress_while_the_rollback_is_signed() {
+        with_externalities(&mut new_test_ext(), || {
+            let ingress = verify_ingress(0);
+            Matrix::expire_transfers(11);
+            assert_eq!(Matrix::release_payload(ingress), None);
+
+            let rollback = hash(&rollback_message(0));
+            assert_eq!(Matrix::rollback_of(rollback), Some(rollback_message(0)));
+            Matrix::verify_rollback_message(validator(1), rollback, vec![1]).unwrap();
+            verify_egress(1);
+
+            // the rollback is cancelled before it is complete.
+            assert_eq!(Matrix::rollback_of(rollback), None);
+            assert!(Matrix::verify_rollback_message(validator(2), rollback, vec![2]).is_err());
+            assert_eq!(Matrix::rollback_payload(rollback), None);
+            assert!(!has_conflict());
+        });
+    }
+
+    #[test]
+    fn egress_after_the_rollback_completed() {
+        with_externalities(&mut new_test_ext(), || {
+            let ingress = verify_ingress(0);
+            Matrix::expire_transfers(11);
+            let rollback = hash(&rollback_message(0));
+            for n in 1..=3 {
+                Matrix::verify_rollback_message(validator(n), rollback, vec![n]).unwrap();
+            }
+            assert!(Matrix::rollback_payload(rollback).is_some());
+            assert_eq!(Matrix::release_payload(ingress), None);
+            assert!(!has_conflict());
+
+            verify_egress(1);
+            assert!(has_conflict());
+        });
+    }
+
+    #[test]
+    fn transfers_of_one_transaction_are_rolled_back_apart() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = verify_ingress(0);
+            let second = verify_ingress(1);
+            verify_egress(1);
+            Matrix::expire_transfers(11);
+
+            // the egress released the first transfer, the second is due for rollback.
+            assert!(Matrix::release_payload(first).is_some());
+            assert_eq!(Matrix::release_payload(second), None);
+            assert_eq!(Matrix::rollback_of(hash(&rollback_message(0))), None);
+            let rollback = hash(&rollback_message(1));
+            assert_eq!(Matrix::rollback_of(rollback), Some(rollback_message(1)));
+
+            // the egress of the second transfer cancels its rollback.
+            verify_egress(2);
+            assert_eq!(Matrix::rollback_of(rollback), None);
+            assert!(!has_conflict());
+        });
+    }
+
+    #[test]
+    fn signatures_are_concatenated_in_validator_order() {
+        with_externalities(&mut new_test_ext(), || {
+            let signatures = vec![
+                (validator(9), vec![9u8]),
+                (validator(3), vec![3u8, 3]),
+                (validator(1), vec![1u8, 1]),
+            ];
+            // accounts that are not validators come last
+            assert_eq!(Matrix::concat_signatures(signatures), vec![1, 1, 3, 3, 9]);
+        });
+    }
+
+    #[test]
+    fn release_payload_once_enough_validators_signed() {
+        with_externalities(&mut new_test_ext(), || {
+            let message = ingress_message(0);
+            for n in &[3, 2] {
+                Matrix::verify_ingress_message(validator(*n), hash(&message), message.clone(), vec![*n]).unwrap();
+                assert_eq!(Matrix::release_payload(hash(&message)), None);
+            }
+            Matrix::verify_ingress_message(validator(1), hash(&message), message.clone(), vec![1]).unwrap();
+            assert_eq!(Matrix::release_payload(hash(&message)), Some((message.clone(), vec![1, 2, 3])));
+            assert!(Matrix::verify_ingress_message(validator(1), hash(&message), message.clone(), vec![1]).is_err());
+            let released = Event::matrix(RawEvent::Ingress(message, vec![1, 2, 3]));
+            assert!(System::events().iter().any(|record| record.event == released));
+        });
+    }
+
+    fn authority_message(last: &[H160], next: &[H160], block_number: u64) -> Vec<u8> {
+        let mut message = vec![1u8; 32]; // coin
+        for authorities in &[last, next] {
+            message.extend_from_slice(&(authorities.len() as u32).to_le_bytes());
+            for authority in authorities.iter() {
+                message.extend_from_slice(&authority[..]);
+            }
+        }
+        message.extend_from_slice(&[2u8; 32]); // transaction hash
+        message.extend_from_slice(&uint256(block_number)); // block number
+        message.extend_from_slice(&[3u8; 32]); // block hash
+        message.extend_from_slice(&uint256(4)); // log index
+        message
+    }
+
+    #[test]
+    fn authority_message_decodes_its_counts() {
+        let (a, b) = (H160::from([0xaa; 20]), H160::from([0xbb; 20]));
+        let message = authority_message(&[a], &[a, b], 7);
+        let decoded = AuthorityMessage::decode(&message).unwrap();
+        assert_eq!(decoded.coin, H256::from([1u8; 32]));
+        assert_eq!((decoded.last, decoded.next), (vec![a], vec![a, b]));
+        assert_eq!((decoded.block_number, decoded.log_index), (7, 4));
+
+        assert_eq!(AuthorityMessage::decode(&message[..message.len() - 1]), None);
+        assert_eq!(AuthorityMessage::decode(&message[..AUTHORITY_MINIMUM_LENGTH - 1]), None);
+        let mut longer = message.clone();
+        longer.push(0);
+        assert_eq!(AuthorityMessage::decode(&longer), None);
+
+        // counts of more addresses than the message holds
+        let mut oversized = message.clone();
+        oversized[32..36].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(AuthorityMessage::decode(&oversized), None);
+        oversized[32..36].copy_from_slice(&u32::max_value().to_le_bytes());
+        assert_eq!(AuthorityMessage::decode(&oversized), None);
+        // a count of fewer addresses than the message holds
+        let mut undersized = message.clone();
+        undersized[56..60].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(AuthorityMessage::decode(&undersized), None);
+
+        // a block number beyond u64
+        let mut overflow = message;
+        overflow[132] = 1;
+        assert_eq!(AuthorityMessage::decode(&overflow), None);
+    }
+
+    #[test]
+    fn reset_authorities_once_enough_validators_signed() {
+        with_externalities(&mut new_test_ext(), || {
+            let (a, b, c) = (H160::from([0xaa; 20]), H160::from([0xbb; 20]), H160::from([0xcc; 20]));
+            let coin = H256::from([1u8; 32]);
+            let reset = |message: &Vec<u8>, n: u8| {
+                let decoded = AuthorityMessage::decode(message).unwrap();
+                Matrix::verify_authority_message(validator(n), hash(message), decoded, vec![n])
+            };
+
+            let message = authority_message(&[a], &[b], 7);
+            for n in 1..=2 {
+                reset(&message, n).unwrap();
+                assert!(Matrix::side_authorities(coin).is_empty());
+            }
+            reset(&message, 3).unwrap();
+            assert_eq!(Matrix::side_authorities(coin), vec![b]);
+            assert_eq!(Matrix::side_authorities_since(coin), Some(7));
+            assert_eq!(reset(&message, 1), Err("has been reset"));
+
+            assert_eq!(reset(&authority_message(&[b], &[c], 6), 1), Err("authorities are outdated"));
+            assert_eq!(reset(&authority_message(&[a], &[c], 8), 1), Err("last authorities do not match"));
+            assert_eq!(reset(&authority_message(&[b], &[c], 8), 1), Ok(()));
+        });
+    }
+
+    fn bind(n: u8, address: u8) {
+        Relayer::set_address(Origin::ROOT, validator(n), H160::from([address; 20])).unwrap();
+    }
+
+    #[test]
+    fn authority_set_of_a_new_session() {
+        with_externalities(&mut new_test_ext(), || {
+            for n in 1..=3 {
+                bind(n, n);
+            }
+            Matrix::update_authority_set();
+            let addresses: Vec<H160> = (1..=3).map(|n| H160::from([n; 20])).collect();
+            let message = authority_set_message(1, 3, &addresses);
+            assert_eq!(Matrix::authority_set_id(), 1);
+            assert_eq!(Matrix::pending_authority_set(), Some(message.clone()));
+            let due = Event::matrix(RawEvent::AuthoritySetDue(message.clone()));
+            assert!(System::events().iter().any(|record| record.event == due));
+
+            let other = authority_set_message(2, 3, &addresses);
+            assert_eq!(Matrix::verify_authority_set(validator(1), other, vec![1]), Err("authority set is not due"));
+            for n in &[2, 1] {
+                Matrix::verify_authority_set(validator(*n), message.clone(), vec![*n]).unwrap();
+                assert!(Matrix::pending_authority_set().is_some());
+            }
+            Matrix::verify_authority_set(validator(3), message.clone(), vec![3]).unwrap();
+            assert_eq!(Matrix::pending_authority_set(), None);
+            let signed = Event::matrix(RawEvent::SetAuthorities(message, vec![1, 2, 3]));
+            assert!(System::events().iter().any(|record| record.event == signed));
+
+            // a new session with the same addresses keeps the set
+            <session::CurrentIndex<Runtime>>::put(1);
+            Matrix::update_authority_set();
+            assert_eq!(Matrix::authority_set_id(), 1);
+
+            // an address bound within a session joins at the next one
+            bind(3, 4);
+            Matrix::update_authority_set();
+            assert_eq!(Matrix::authority_set_id(), 1);
+            <session::CurrentIndex<Runtime>>::put(2);
+            Matrix::update_authority_set();
+            assert_eq!(Matrix::authority_set_id(), 2);
+            assert_eq!(Matrix::authority_set_addresses()[2], H160::from([4; 20]));
+        });
+    }
+}